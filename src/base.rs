@@ -0,0 +1,84 @@
+use errors::Error;
+
+/// A single piece of input handed to the tokenizer: the bytes a
+/// `TransformStream` write accumulated, plus whether it is the last one, with
+/// no more input to follow (`write()` chunks never are, `end()`'s chunk always is).
+pub struct Chunk<'c> {
+    bytes: &'c [u8],
+    last: bool,
+}
+
+impl<'c> Chunk<'c> {
+    pub fn last(bytes: &'c [u8]) -> Self {
+        Chunk { bytes, last: true }
+    }
+
+    pub fn last_empty() -> Self {
+        Chunk {
+            bytes: &[],
+            last: true,
+        }
+    }
+
+    pub fn bytes(&self) -> &'c [u8] {
+        self.bytes
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+}
+
+impl<'c> From<&'c [u8]> for Chunk<'c> {
+    fn from(bytes: &'c [u8]) -> Self {
+        Chunk { bytes, last: false }
+    }
+}
+
+/// Holds the tail of a write that the tokenizer couldn't yet turn into a
+/// complete lex unit (e.g. a tag that hasn't seen its closing `>`), so it
+/// can be prepended to the next chunk.
+pub struct Buffer {
+    capacity: usize,
+    bytes: Vec<u8>,
+}
+
+impl Buffer {
+    pub fn new(capacity: usize) -> Self {
+        Buffer {
+            capacity,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn init_with(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.bytes.clear();
+        self.append(bytes)
+    }
+
+    pub fn append(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if self.bytes.len() + bytes.len() > self.capacity {
+            return Err(Error::BufferCapacityExceeded);
+        }
+
+        self.bytes.extend_from_slice(bytes);
+
+        Ok(())
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Drops everything except the last `len` bytes, keeping only the
+    /// still-unconsumed tail that blocked tokenization.
+    pub fn shrink_to_last(&mut self, len: usize) {
+        let start = self.bytes.len() - len;
+
+        self.bytes.drain(..start);
+    }
+}
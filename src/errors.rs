@@ -0,0 +1,23 @@
+use std::error;
+use std::fmt;
+
+/// Fatal errors that abort tokenization outright, as opposed to the
+/// non-fatal `ParseError`s reported through the error sink.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    BufferCapacityExceeded,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BufferCapacityExceeded => write!(f, "write would exceed buffer capacity"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "transform stream error"
+    }
+}
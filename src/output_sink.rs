@@ -0,0 +1,7 @@
+/// Receives the bytes a `TransformStream` serializes as it rewrites input.
+///
+/// Mirrors `LexUnitHandler`/`ParseErrorHandler`: any `FnMut(&[u8])` closure
+/// qualifies.
+pub trait OutputSink: FnMut(&[u8]) {}
+
+impl<F: FnMut(&[u8])> OutputSink for F {}
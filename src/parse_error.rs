@@ -0,0 +1,115 @@
+/// A non-fatal spec violation raised by the tokenizer while it keeps parsing.
+///
+/// These mirror the `errors` entries in the html5lib-tests corpus: a named
+/// spec error code plus the byte offset (translated to line/column) at which
+/// it was raised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorCode {
+    UnexpectedNullCharacter,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedCharacterInUnquotedAttributeValue,
+    DuplicateAttribute,
+    EofInTag,
+    EofInScriptHtmlCommentLikeText,
+    EofBeforeTagName,
+    MissingAttributeValue,
+    MissingSemicolonAfterCharacterReference,
+    MissingWhitespaceBetweenAttributes,
+    UnknownNamedCharacterReference,
+    AbsenceOfDigitsInNumericCharacterReference,
+    ControlCharacterReference,
+    NullCharacterReference,
+    CharacterReferenceOutsideUnicodeRange,
+    SurrogateCharacterReference,
+    NoncharacterCharacterReference,
+}
+
+impl ParseErrorCode {
+    /// Returns the spec's kebab-case error code, as used by html5lib-tests.
+    pub fn code(self) -> &'static str {
+        use self::ParseErrorCode::*;
+
+        match self {
+            UnexpectedNullCharacter => "unexpected-null-character",
+            UnexpectedCharacterInAttributeName => "unexpected-character-in-attribute-name",
+            UnexpectedCharacterInUnquotedAttributeValue => {
+                "unexpected-character-in-unquoted-attribute-value"
+            }
+            DuplicateAttribute => "duplicate-attribute",
+            EofInTag => "eof-in-tag",
+            EofInScriptHtmlCommentLikeText => "eof-in-script-html-comment-like-text",
+            EofBeforeTagName => "eof-before-tag-name",
+            MissingAttributeValue => "missing-attribute-value",
+            MissingSemicolonAfterCharacterReference => {
+                "missing-semicolon-after-character-reference"
+            }
+            MissingWhitespaceBetweenAttributes => "missing-whitespace-between-attributes",
+            UnknownNamedCharacterReference => "unknown-named-character-reference",
+            AbsenceOfDigitsInNumericCharacterReference => {
+                "absence-of-digits-in-numeric-character-reference"
+            }
+            ControlCharacterReference => "control-character-reference",
+            NullCharacterReference => "null-character-reference",
+            CharacterReferenceOutsideUnicodeRange => "character-reference-outside-unicode-range",
+            SurrogateCharacterReference => "surrogate-character-reference",
+            NoncharacterCharacterReference => "noncharacter-character-reference",
+        }
+    }
+}
+
+/// 1-based line/column position within the overall byte stream fed to a
+/// `TransformStream`, tracked across chunk boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl Position {
+    /// Advances the position past `bytes`, as if they had just been fed to
+    /// the tokenizer. html5lib-tests positions are per code point, not per
+    /// byte, so only a UTF-8 sequence's lead byte counts towards `col`; a
+    /// continuation byte (regardless of which side of a chunk boundary it
+    /// falls on) is skipped.
+    pub fn advance(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.line += 1;
+                self.col = 1;
+            } else if !is_utf8_continuation_byte(byte) {
+                self.col += 1;
+            }
+        }
+    }
+}
+
+fn is_utf8_continuation_byte(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position { line: 1, col: 1 }
+    }
+}
+
+/// A single non-fatal parse error, raised by the tokenizer at a given
+/// position, without aborting tokenization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    pub code: ParseErrorCode,
+    pub position: Position,
+}
+
+impl ParseError {
+    pub fn new(code: ParseErrorCode, position: Position) -> Self {
+        ParseError { code, position }
+    }
+}
+
+/// Receives non-fatal parse errors as the tokenizer encounters them.
+///
+/// Mirrors `LexUnitHandler`: any `FnMut(ParseError)` closure qualifies.
+pub trait ParseErrorHandler: FnMut(ParseError) {}
+
+impl<F: FnMut(ParseError)> ParseErrorHandler for F {}
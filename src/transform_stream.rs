@@ -1,21 +1,50 @@
 use base::{Buffer, Chunk};
 use errors::Error;
-use tokenizer::{LexUnitHandler, Tokenizer};
+use output_sink::OutputSink;
+use parse_error::{ParseErrorHandler, Position};
+use tokenizer::{LexUnitHandler, TagLexUnitHandler, TagPreviewHandler, TextParsingModeSnapshot, Tokenizer};
 
-pub struct TransformStream<H> {
-    tokenizer: Tokenizer<H>,
+pub struct TransformStream<H, TH, TP, E, O> {
+    tokenizer: Tokenizer<H, TH, TP, E, O>,
     buffer: Buffer,
     has_buffered_data: bool,
     finished: bool,
+    // Position of the first byte of whatever is currently buffered, so that a
+    // chunk assembled from buffered + fresh bytes can still report accurate
+    // line/column positions for errors raised anywhere in it.
+    buffer_start_pos: Position,
+    pos: Position,
 }
 
-impl<H: LexUnitHandler> TransformStream<H> {
-    pub fn new(buffer_capacity: usize, lex_unit_handler: H) -> Self {
+impl<H, TH, TP, E, O> TransformStream<H, TH, TP, E, O>
+where
+    H: LexUnitHandler,
+    TH: TagLexUnitHandler,
+    TP: TagPreviewHandler,
+    E: ParseErrorHandler,
+    O: OutputSink,
+{
+    pub fn new(
+        buffer_capacity: usize,
+        lex_unit_handler: H,
+        tag_lex_unit_handler: TH,
+        tag_preview_handler: TP,
+        parse_error_handler: E,
+        output_sink: O,
+    ) -> Self {
         TransformStream {
-            tokenizer: Tokenizer::new(lex_unit_handler),
+            tokenizer: Tokenizer::new(
+                lex_unit_handler,
+                tag_lex_unit_handler,
+                tag_preview_handler,
+                parse_error_handler,
+                output_sink,
+            ),
             buffer: Buffer::new(buffer_capacity),
             has_buffered_data: false,
             finished: false,
+            buffer_start_pos: Position::default(),
+            pos: Position::default(),
         }
     }
 
@@ -23,6 +52,22 @@ impl<H: LexUnitHandler> TransformStream<H> {
         assert!(!self.finished, "Attempt to call write() after end()");
         trace!(@write data);
 
+        let chunk_start_pos = if self.has_buffered_data {
+            self.buffer_start_pos
+        } else {
+            self.pos
+        };
+
+        let data_start_pos = self.pos;
+
+        self.pos.advance(data);
+
+        // Everything up to the newly blocked suffix is confirmed: no later
+        // byte can still cause it to be reinterpreted, so the tokenizer has
+        // already written it to the output sink, token by token, as it
+        // resolved each one (verbatim `raw` when a handler left a `LexUnit`
+        // alone, `output` otherwise). Only the still-blocked suffix, which
+        // produced no tokens yet, remains to be buffered below.
         let blocked_byte_count = {
             let chunk = if self.has_buffered_data {
                 self.buffer.append(data)?;
@@ -33,18 +78,32 @@ impl<H: LexUnitHandler> TransformStream<H> {
 
             trace!(@chunk chunk);
 
-            self.tokenizer.tokenize(&chunk)?
+            self.tokenizer.tokenize(&chunk, chunk_start_pos)?
         };
 
         let need_to_buffer = blocked_byte_count > 0;
 
         if need_to_buffer {
             if self.has_buffered_data {
+                // The retained suffix still starts somewhere inside the
+                // already-buffered bytes, so re-derive its position by
+                // walking forward from where the buffer used to start,
+                // before the buffer is shrunk down to just that suffix.
+                let discarded_prefix_len = self.buffer.bytes().len() - blocked_byte_count;
+                let mut new_start = self.buffer_start_pos;
+
+                new_start.advance(&self.buffer.bytes()[..discarded_prefix_len]);
+
                 self.buffer.shrink_to_last(blocked_byte_count);
+                self.buffer_start_pos = new_start;
             } else {
                 let blocked_bytes = &data[data.len() - blocked_byte_count..];
+                let mut new_start = data_start_pos;
+
+                new_start.advance(&data[..data.len() - blocked_byte_count]);
 
                 self.buffer.init_with(blocked_bytes)?;
+                self.buffer_start_pos = new_start;
             }
 
             trace!(@buffer self.buffer);
@@ -61,6 +120,12 @@ impl<H: LexUnitHandler> TransformStream<H> {
 
         self.finished = true;
 
+        let chunk_start_pos = if self.has_buffered_data {
+            self.buffer_start_pos
+        } else {
+            self.pos
+        };
+
         let chunk = if self.has_buffered_data {
             Chunk::last(self.buffer.bytes())
         } else {
@@ -69,13 +134,30 @@ impl<H: LexUnitHandler> TransformStream<H> {
 
         trace!(@chunk chunk);
 
-        self.tokenizer.tokenize(&chunk)?;
+        self.tokenizer.tokenize(&chunk, chunk_start_pos)?;
 
         Ok(())
     }
 
     #[cfg(feature = "testing_api")]
-    pub fn get_tokenizer(&mut self) -> &mut Tokenizer<H> {
+    pub fn get_tokenizer(&mut self) -> &mut Tokenizer<H, TH, TP, E, O> {
         &mut self.tokenizer
     }
+
+    /// Switches the tokenizer into `snapshot`'s content model state before
+    /// any byte has been fed to it, so that e.g. RCDATA/RAWTEXT end-tag
+    /// matching behaves correctly from the start of the input rather than
+    /// only after observing a matching start tag.
+    #[cfg(feature = "testing_api")]
+    pub fn set_initial_text_parsing_mode(&mut self, snapshot: TextParsingModeSnapshot) {
+        self.tokenizer.set_text_parsing_mode_snapshot(snapshot);
+    }
+
+    /// Sets the name of the last start tag the tokenizer should behave as if
+    /// it had already seen, so that e.g. `</script>` is recognised as the
+    /// matching end tag for RAWTEXT/RCDATA content fed from the first byte.
+    #[cfg(feature = "testing_api")]
+    pub fn set_last_start_tag_name(&mut self, name: &str) {
+        self.tokenizer.set_last_start_tag_name(name);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,667 @@
+use cool_thing::parse_error::ParseErrorCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Text,
+    Attribute,
+}
+
+/// Decodes the raw, as-lexed text captured by the tokenizer (comment data,
+/// character data, attribute values) into the string a spec-compliant parser
+/// would hand to the DOM, so it can be compared against html5lib-tests'
+/// expected token output.
+///
+/// This mirrors (a subset of) the WHATWG "named character reference state"
+/// and "numeric character reference end state" algorithms. It's test-harness
+/// plumbing only: the tokenizer itself never expands entities, it just
+/// records raw slices, which is why this lives next to the test token types
+/// rather than in the `cool_thing` crate.
+pub struct Decoder<'i> {
+    input: &'i str,
+    unsafe_null: bool,
+    decode_references: bool,
+    mode: Mode,
+}
+
+impl<'i> Decoder<'i> {
+    pub fn new(input: &'i str) -> Self {
+        Decoder {
+            input,
+            unsafe_null: false,
+            decode_references: true,
+            mode: Mode::Text,
+        }
+    }
+
+    /// Replaces raw `\0` bytes with U+FFFD. The tokenizer keeps NULs as they
+    /// are in `raw` and attribute values (it only raises
+    /// `UnexpectedNullCharacter` for them), so the replacement happens here.
+    pub fn unsafe_null(mut self) -> Self {
+        self.unsafe_null = true;
+        self
+    }
+
+    /// Switches on the attribute-value character reference rules: a named
+    /// reference that isn't terminated by `;` is only expanded if it isn't
+    /// immediately followed by `=` or an alphanumeric character.
+    pub fn attr_entities(mut self) -> Self {
+        self.mode = Mode::Attribute;
+        self
+    }
+
+    /// Turns off named/numeric character-reference expansion entirely, as
+    /// required for comment data: unlike character and attribute data, a
+    /// comment's `&...` sequences are never treated as references and must
+    /// come through as literal text.
+    pub fn no_character_references(mut self) -> Self {
+        self.decode_references = false;
+        self
+    }
+
+    pub fn run(self) -> (String, Vec<ParseErrorCode>) {
+        let mut out = String::with_capacity(self.input.len());
+        let mut errors = Vec::new();
+        let mut chars = self.input.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            if c == '\0' && self.unsafe_null {
+                out.push('\u{FFFD}');
+                continue;
+            }
+
+            if c != '&' || !self.decode_references {
+                out.push(c);
+                continue;
+            }
+
+            let rest = &self.input[i + 1..];
+
+            if rest.starts_with('#') {
+                let (decoded, consumed, mut numeric_errors) = decode_numeric_reference(rest);
+
+                out.push(decoded);
+                errors.append(&mut numeric_errors);
+                advance_by(&mut chars, consumed);
+            } else if let Some((replacement, consumed, had_semicolon)) =
+                match_named_reference(rest)
+            {
+                let next_byte = rest.as_bytes().get(consumed).copied();
+                let leave_as_literal = !had_semicolon
+                    && self.mode == Mode::Attribute
+                    && next_byte.map_or(false, |b| b == b'=' || b.is_ascii_alphanumeric());
+
+                if leave_as_literal {
+                    out.push('&');
+                } else {
+                    if !had_semicolon {
+                        errors.push(ParseErrorCode::MissingSemicolonAfterCharacterReference);
+                    }
+
+                    out.push_str(replacement);
+                    advance_by(&mut chars, consumed);
+                }
+            } else {
+                // Ambiguous ampersand state: a run of ASCII alphanumerics
+                // that doesn't match any named reference is only a parse
+                // error when it's `;`-terminated -- either way it's left as
+                // literal text, the same as if the outer loop had just
+                // walked over these characters one at a time.
+                let alnum_len = rest.chars().take_while(|c| c.is_ascii_alphanumeric()).count();
+
+                if rest.chars().nth(alnum_len) == Some(';') {
+                    errors.push(ParseErrorCode::UnknownNamedCharacterReference);
+                }
+
+                out.push('&');
+            }
+        }
+
+        (out, errors)
+    }
+}
+
+fn advance_by<I: Iterator>(chars: &mut I, count: usize) {
+    for _ in 0..count {
+        chars.next();
+    }
+}
+
+/// Parses a `#`-prefixed numeric character reference (the `#` itself is part
+/// of `input`). Returns the decoded character, the number of input chars
+/// consumed *after* the leading `&`, and any parse errors.
+fn decode_numeric_reference(input: &str) -> (char, usize, Vec<ParseErrorCode>) {
+    let mut errors = Vec::new();
+    let bytes = &input[1..];
+    let is_hex = bytes.starts_with('x') || bytes.starts_with('X');
+    let digits_start = if is_hex { 1 } else { 0 };
+
+    let digits_len = bytes[digits_start..]
+        .chars()
+        .take_while(|c| if is_hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() })
+        .count();
+
+    if digits_len == 0 {
+        errors.push(ParseErrorCode::AbsenceOfDigitsInNumericCharacterReference);
+
+        // Nothing consumed beyond the `&`; the `#`/`#x` prefix is left as text.
+        return ('&', 0, errors);
+    }
+
+    let digits = &bytes[digits_start..digits_start + digits_len];
+    let radix = if is_hex { 16 } else { 10 };
+    let code_point = u32::from_str_radix(digits, radix).unwrap_or(0x11_0000);
+
+    let mut consumed = 1 + digits_start + digits_len;
+
+    if input[1 + digits_start + digits_len..].starts_with(';') {
+        consumed += 1;
+    } else {
+        errors.push(ParseErrorCode::MissingSemicolonAfterCharacterReference);
+    }
+
+    let decoded = match code_point {
+        0x00 => {
+            errors.push(ParseErrorCode::NullCharacterReference);
+            '\u{FFFD}'
+        }
+        0x80..=0x9F => {
+            let replacement = C1_REPLACEMENTS[(code_point - 0x80) as usize];
+
+            errors.push(ParseErrorCode::ControlCharacterReference);
+            replacement
+        }
+        0xD800..=0xDFFF => {
+            errors.push(ParseErrorCode::SurrogateCharacterReference);
+            '\u{FFFD}'
+        }
+        cp if cp > 0x10_FFFF => {
+            errors.push(ParseErrorCode::CharacterReferenceOutsideUnicodeRange);
+            '\u{FFFD}'
+        }
+        cp if is_noncharacter(cp) => {
+            errors.push(ParseErrorCode::NoncharacterCharacterReference);
+            ::std::char::from_u32(cp).unwrap_or('\u{FFFD}')
+        }
+        cp if is_control(cp) => {
+            errors.push(ParseErrorCode::ControlCharacterReference);
+            ::std::char::from_u32(cp).unwrap_or('\u{FFFD}')
+        }
+        cp => ::std::char::from_u32(cp).unwrap_or('\u{FFFD}'),
+    };
+
+    (decoded, consumed, errors)
+}
+
+fn is_noncharacter(cp: u32) -> bool {
+    (0xFDD0..=0xFDEF).contains(&cp) || (cp & 0xFFFE) == 0xFFFE
+}
+
+fn is_control(cp: u32) -> bool {
+    let is_c0_control_other_than_whitespace = cp <= 0x1F && cp != 0x09 && cp != 0x0A && cp != 0x0C;
+
+    is_c0_control_other_than_whitespace || (0x7F..=0x9F).contains(&cp)
+}
+
+/// Windows-1252 override table used by the spec for C1-range numeric
+/// character references (`&#128;` through `&#159;`).
+const C1_REPLACEMENTS: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Attempts the longest match of `input` (everything after the `&`) against
+/// the named character reference table. Returns the replacement text, the
+/// number of input chars matched (after the `&`), and whether the match was
+/// terminated by a `;`.
+///
+/// This table is a deliberate subset, NOT the full ~2231-entry WHATWG named
+/// character reference table: the complete set of legacy (no-semicolon-
+/// required) HTML4 entities, plus the semicolon-required references
+/// commonly exercised by the html5lib-tests tokenizer suite (Greek letters,
+/// general punctuation, arrows, math operators). A reference outside this
+/// list falls through to literal text instead of decoding, so test cases
+/// exercising an entity this table doesn't know about are an accepted gap
+/// rather than a bug -- extend the table below if a real case needs one.
+/// Longest match picks correctly between a legacy no-semicolon prefix and a
+/// longer semicolon-terminated name sharing that prefix (e.g. `&notin`
+/// falls back to `not` while `&notin;` matches in full).
+fn match_named_reference(input: &str) -> Option<(&'static str, usize, bool)> {
+    NAMED_REFERENCES
+        .iter()
+        .filter(|&&(name, _)| input.starts_with(name))
+        .max_by_key(|&&(name, _)| name.len())
+        .map(|&(name, replacement)| (replacement, name.len(), name.ends_with(';')))
+}
+
+const NAMED_REFERENCES: &[(&str, &str)] = &[
+    // Legacy (no-semicolon-required) HTML4 entities, both forms.
+    ("AElig;", "\u{00C6}"),
+    ("AElig", "\u{00C6}"),
+    ("AMP;", "\u{0026}"),
+    ("AMP", "\u{0026}"),
+    ("Aacute;", "\u{00C1}"),
+    ("Aacute", "\u{00C1}"),
+    ("Acirc;", "\u{00C2}"),
+    ("Acirc", "\u{00C2}"),
+    ("Agrave;", "\u{00C0}"),
+    ("Agrave", "\u{00C0}"),
+    ("Aring;", "\u{00C5}"),
+    ("Aring", "\u{00C5}"),
+    ("Atilde;", "\u{00C3}"),
+    ("Atilde", "\u{00C3}"),
+    ("Auml;", "\u{00C4}"),
+    ("Auml", "\u{00C4}"),
+    ("COPY;", "\u{00A9}"),
+    ("COPY", "\u{00A9}"),
+    ("Ccedil;", "\u{00C7}"),
+    ("Ccedil", "\u{00C7}"),
+    ("ETH;", "\u{00D0}"),
+    ("ETH", "\u{00D0}"),
+    ("Eacute;", "\u{00C9}"),
+    ("Eacute", "\u{00C9}"),
+    ("Ecirc;", "\u{00CA}"),
+    ("Ecirc", "\u{00CA}"),
+    ("Egrave;", "\u{00C8}"),
+    ("Egrave", "\u{00C8}"),
+    ("Euml;", "\u{00CB}"),
+    ("Euml", "\u{00CB}"),
+    ("GT;", "\u{003E}"),
+    ("GT", "\u{003E}"),
+    ("Iacute;", "\u{00CD}"),
+    ("Iacute", "\u{00CD}"),
+    ("Icirc;", "\u{00CE}"),
+    ("Icirc", "\u{00CE}"),
+    ("Igrave;", "\u{00CC}"),
+    ("Igrave", "\u{00CC}"),
+    ("Iuml;", "\u{00CF}"),
+    ("Iuml", "\u{00CF}"),
+    ("LT;", "\u{003C}"),
+    ("LT", "\u{003C}"),
+    ("Ntilde;", "\u{00D1}"),
+    ("Ntilde", "\u{00D1}"),
+    ("Oacute;", "\u{00D3}"),
+    ("Oacute", "\u{00D3}"),
+    ("Ocirc;", "\u{00D4}"),
+    ("Ocirc", "\u{00D4}"),
+    ("Ograve;", "\u{00D2}"),
+    ("Ograve", "\u{00D2}"),
+    ("Oslash;", "\u{00D8}"),
+    ("Oslash", "\u{00D8}"),
+    ("Otilde;", "\u{00D5}"),
+    ("Otilde", "\u{00D5}"),
+    ("Ouml;", "\u{00D6}"),
+    ("Ouml", "\u{00D6}"),
+    ("QUOT;", "\u{0022}"),
+    ("QUOT", "\u{0022}"),
+    ("REG;", "\u{00AE}"),
+    ("REG", "\u{00AE}"),
+    ("THORN;", "\u{00DE}"),
+    ("THORN", "\u{00DE}"),
+    ("Uacute;", "\u{00DA}"),
+    ("Uacute", "\u{00DA}"),
+    ("Ucirc;", "\u{00DB}"),
+    ("Ucirc", "\u{00DB}"),
+    ("Ugrave;", "\u{00D9}"),
+    ("Ugrave", "\u{00D9}"),
+    ("Uuml;", "\u{00DC}"),
+    ("Uuml", "\u{00DC}"),
+    ("Yacute;", "\u{00DD}"),
+    ("Yacute", "\u{00DD}"),
+    ("aacute;", "\u{00E1}"),
+    ("aacute", "\u{00E1}"),
+    ("acirc;", "\u{00E2}"),
+    ("acirc", "\u{00E2}"),
+    ("acute;", "\u{00B4}"),
+    ("acute", "\u{00B4}"),
+    ("aelig;", "\u{00E6}"),
+    ("aelig", "\u{00E6}"),
+    ("agrave;", "\u{00E0}"),
+    ("agrave", "\u{00E0}"),
+    ("amp;", "\u{0026}"),
+    ("amp", "\u{0026}"),
+    ("apos;", "\u{0027}"),
+    ("aring;", "\u{00E5}"),
+    ("aring", "\u{00E5}"),
+    ("atilde;", "\u{00E3}"),
+    ("atilde", "\u{00E3}"),
+    ("auml;", "\u{00E4}"),
+    ("auml", "\u{00E4}"),
+    ("brvbar;", "\u{00A6}"),
+    ("brvbar", "\u{00A6}"),
+    ("ccedil;", "\u{00E7}"),
+    ("ccedil", "\u{00E7}"),
+    ("cedil;", "\u{00B8}"),
+    ("cedil", "\u{00B8}"),
+    ("cent;", "\u{00A2}"),
+    ("cent", "\u{00A2}"),
+    ("copy;", "\u{00A9}"),
+    ("copy", "\u{00A9}"),
+    ("curren;", "\u{00A4}"),
+    ("curren", "\u{00A4}"),
+    ("deg;", "\u{00B0}"),
+    ("deg", "\u{00B0}"),
+    ("divide;", "\u{00F7}"),
+    ("divide", "\u{00F7}"),
+    ("eacute;", "\u{00E9}"),
+    ("eacute", "\u{00E9}"),
+    ("ecirc;", "\u{00EA}"),
+    ("ecirc", "\u{00EA}"),
+    ("egrave;", "\u{00E8}"),
+    ("egrave", "\u{00E8}"),
+    ("eth;", "\u{00F0}"),
+    ("eth", "\u{00F0}"),
+    ("euml;", "\u{00EB}"),
+    ("euml", "\u{00EB}"),
+    ("frac12;", "\u{00BD}"),
+    ("frac12", "\u{00BD}"),
+    ("frac14;", "\u{00BC}"),
+    ("frac14", "\u{00BC}"),
+    ("frac34;", "\u{00BE}"),
+    ("frac34", "\u{00BE}"),
+    ("gt;", "\u{003E}"),
+    ("gt", "\u{003E}"),
+    ("iacute;", "\u{00ED}"),
+    ("iacute", "\u{00ED}"),
+    ("icirc;", "\u{00EE}"),
+    ("icirc", "\u{00EE}"),
+    ("iexcl;", "\u{00A1}"),
+    ("iexcl", "\u{00A1}"),
+    ("igrave;", "\u{00EC}"),
+    ("igrave", "\u{00EC}"),
+    ("iquest;", "\u{00BF}"),
+    ("iquest", "\u{00BF}"),
+    ("iuml;", "\u{00EF}"),
+    ("iuml", "\u{00EF}"),
+    ("laquo;", "\u{00AB}"),
+    ("laquo", "\u{00AB}"),
+    ("lt;", "\u{003C}"),
+    ("lt", "\u{003C}"),
+    ("macr;", "\u{00AF}"),
+    ("macr", "\u{00AF}"),
+    ("micro;", "\u{00B5}"),
+    ("micro", "\u{00B5}"),
+    ("middot;", "\u{00B7}"),
+    ("middot", "\u{00B7}"),
+    ("nbsp;", "\u{00A0}"),
+    ("nbsp", "\u{00A0}"),
+    ("not;", "\u{00AC}"),
+    ("not", "\u{00AC}"),
+    ("ntilde;", "\u{00F1}"),
+    ("ntilde", "\u{00F1}"),
+    ("oacute;", "\u{00F3}"),
+    ("oacute", "\u{00F3}"),
+    ("ocirc;", "\u{00F4}"),
+    ("ocirc", "\u{00F4}"),
+    ("ograve;", "\u{00F2}"),
+    ("ograve", "\u{00F2}"),
+    ("ordf;", "\u{00AA}"),
+    ("ordf", "\u{00AA}"),
+    ("ordm;", "\u{00BA}"),
+    ("ordm", "\u{00BA}"),
+    ("oslash;", "\u{00F8}"),
+    ("oslash", "\u{00F8}"),
+    ("otilde;", "\u{00F5}"),
+    ("otilde", "\u{00F5}"),
+    ("ouml;", "\u{00F6}"),
+    ("ouml", "\u{00F6}"),
+    ("para;", "\u{00B6}"),
+    ("para", "\u{00B6}"),
+    ("plusmn;", "\u{00B1}"),
+    ("plusmn", "\u{00B1}"),
+    ("pound;", "\u{00A3}"),
+    ("pound", "\u{00A3}"),
+    ("quot;", "\u{0022}"),
+    ("quot", "\u{0022}"),
+    ("raquo;", "\u{00BB}"),
+    ("raquo", "\u{00BB}"),
+    ("reg;", "\u{00AE}"),
+    ("reg", "\u{00AE}"),
+    ("sect;", "\u{00A7}"),
+    ("sect", "\u{00A7}"),
+    ("shy;", "\u{00AD}"),
+    ("shy", "\u{00AD}"),
+    ("sup1;", "\u{00B9}"),
+    ("sup1", "\u{00B9}"),
+    ("sup2;", "\u{00B2}"),
+    ("sup2", "\u{00B2}"),
+    ("sup3;", "\u{00B3}"),
+    ("sup3", "\u{00B3}"),
+    ("szlig;", "\u{00DF}"),
+    ("szlig", "\u{00DF}"),
+    ("thorn;", "\u{00FE}"),
+    ("thorn", "\u{00FE}"),
+    ("times;", "\u{00D7}"),
+    ("times", "\u{00D7}"),
+    ("uacute;", "\u{00FA}"),
+    ("uacute", "\u{00FA}"),
+    ("ucirc;", "\u{00FB}"),
+    ("ucirc", "\u{00FB}"),
+    ("ugrave;", "\u{00F9}"),
+    ("ugrave", "\u{00F9}"),
+    ("uml;", "\u{00A8}"),
+    ("uml", "\u{00A8}"),
+    ("uuml;", "\u{00FC}"),
+    ("uuml", "\u{00FC}"),
+    ("yacute;", "\u{00FD}"),
+    ("yacute", "\u{00FD}"),
+    ("yen;", "\u{00A5}"),
+    ("yen", "\u{00A5}"),
+    ("yuml;", "\u{00FF}"),
+    ("yuml", "\u{00FF}"),
+    // Semicolon-required references with no legacy HTML4 form: Greek
+    // alphabet, general punctuation, arrows, and common math operators.
+    ("Alpha;", "\u{0391}"),
+    ("Beta;", "\u{0392}"),
+    ("Gamma;", "\u{0393}"),
+    ("Delta;", "\u{0394}"),
+    ("Epsilon;", "\u{0395}"),
+    ("Zeta;", "\u{0396}"),
+    ("Eta;", "\u{0397}"),
+    ("Theta;", "\u{0398}"),
+    ("Iota;", "\u{0399}"),
+    ("Kappa;", "\u{039A}"),
+    ("Lambda;", "\u{039B}"),
+    ("Mu;", "\u{039C}"),
+    ("Nu;", "\u{039D}"),
+    ("Xi;", "\u{039E}"),
+    ("Omicron;", "\u{039F}"),
+    ("Pi;", "\u{03A0}"),
+    ("Rho;", "\u{03A1}"),
+    ("Sigma;", "\u{03A3}"),
+    ("Tau;", "\u{03A4}"),
+    ("Upsilon;", "\u{03A5}"),
+    ("Phi;", "\u{03A6}"),
+    ("Chi;", "\u{03A7}"),
+    ("Psi;", "\u{03A8}"),
+    ("Omega;", "\u{03A9}"),
+    ("alpha;", "\u{03B1}"),
+    ("beta;", "\u{03B2}"),
+    ("gamma;", "\u{03B3}"),
+    ("delta;", "\u{03B4}"),
+    ("epsilon;", "\u{03B5}"),
+    ("zeta;", "\u{03B6}"),
+    ("eta;", "\u{03B7}"),
+    ("theta;", "\u{03B8}"),
+    ("iota;", "\u{03B9}"),
+    ("kappa;", "\u{03BA}"),
+    ("lambda;", "\u{03BB}"),
+    ("mu;", "\u{03BC}"),
+    ("nu;", "\u{03BD}"),
+    ("xi;", "\u{03BE}"),
+    ("omicron;", "\u{03BF}"),
+    ("pi;", "\u{03C0}"),
+    ("rho;", "\u{03C1}"),
+    ("sigmaf;", "\u{03C2}"),
+    ("sigma;", "\u{03C3}"),
+    ("tau;", "\u{03C4}"),
+    ("upsilon;", "\u{03C5}"),
+    ("phi;", "\u{03C6}"),
+    ("chi;", "\u{03C7}"),
+    ("psi;", "\u{03C8}"),
+    ("omega;", "\u{03C9}"),
+    ("euro;", "\u{20AC}"),
+    ("hellip;", "\u{2026}"),
+    ("mdash;", "\u{2014}"),
+    ("ndash;", "\u{2013}"),
+    ("lsquo;", "\u{2018}"),
+    ("rsquo;", "\u{2019}"),
+    ("sbquo;", "\u{201A}"),
+    ("ldquo;", "\u{201C}"),
+    ("rdquo;", "\u{201D}"),
+    ("bdquo;", "\u{201E}"),
+    ("dagger;", "\u{2020}"),
+    ("Dagger;", "\u{2021}"),
+    ("bull;", "\u{2022}"),
+    ("permil;", "\u{2030}"),
+    ("lsaquo;", "\u{2039}"),
+    ("rsaquo;", "\u{203A}"),
+    ("oline;", "\u{203E}"),
+    ("frasl;", "\u{2044}"),
+    ("trade;", "\u{2122}"),
+    ("larr;", "\u{2190}"),
+    ("uarr;", "\u{2191}"),
+    ("rarr;", "\u{2192}"),
+    ("darr;", "\u{2193}"),
+    ("harr;", "\u{2194}"),
+    ("crarr;", "\u{21B5}"),
+    ("forall;", "\u{2200}"),
+    ("part;", "\u{2202}"),
+    ("exist;", "\u{2203}"),
+    ("empty;", "\u{2205}"),
+    ("nabla;", "\u{2207}"),
+    ("isin;", "\u{2208}"),
+    ("notin;", "\u{2209}"),
+    ("ni;", "\u{220B}"),
+    ("prod;", "\u{220F}"),
+    ("sum;", "\u{2211}"),
+    ("minus;", "\u{2212}"),
+    ("lowast;", "\u{2217}"),
+    ("radic;", "\u{221A}"),
+    ("prop;", "\u{221D}"),
+    ("infin;", "\u{221E}"),
+    ("ang;", "\u{2220}"),
+    ("and;", "\u{2227}"),
+    ("or;", "\u{2228}"),
+    ("cap;", "\u{2229}"),
+    ("cup;", "\u{222A}"),
+    ("int;", "\u{222B}"),
+    ("there4;", "\u{2234}"),
+    ("sim;", "\u{223C}"),
+    ("cong;", "\u{2245}"),
+    ("asymp;", "\u{2248}"),
+    ("ne;", "\u{2260}"),
+    ("equiv;", "\u{2261}"),
+    ("le;", "\u{2264}"),
+    ("ge;", "\u{2265}"),
+    ("sub;", "\u{2282}"),
+    ("sup;", "\u{2283}"),
+    ("nsub;", "\u{2284}"),
+    ("sube;", "\u{2286}"),
+    ("supe;", "\u{2287}"),
+    ("oplus;", "\u{2295}"),
+    ("otimes;", "\u{2297}"),
+    ("perp;", "\u{22A5}"),
+    ("sdot;", "\u{22C5}"),
+    ("lceil;", "\u{2308}"),
+    ("rceil;", "\u{2309}"),
+    ("lfloor;", "\u{230A}"),
+    ("rfloor;", "\u{230B}"),
+    ("lang;", "\u{27E8}"),
+    ("rang;", "\u{27E9}"),
+    ("loz;", "\u{25CA}"),
+    ("spades;", "\u{2660}"),
+    ("clubs;", "\u{2663}"),
+    ("hearts;", "\u{2665}"),
+    ("diams;", "\u{2666}"),
+    ("OElig;", "\u{0152}"),
+    ("oelig;", "\u{0153}"),
+    ("Scaron;", "\u{0160}"),
+    ("scaron;", "\u{0161}"),
+    ("Yuml;", "\u{0178}"),
+    ("fnof;", "\u{0192}"),
+    ("circ;", "\u{02C6}"),
+    ("tilde;", "\u{02DC}"),
+    ("ensp;", "\u{2002}"),
+    ("emsp;", "\u{2003}"),
+    ("thinsp;", "\u{2009}"),
+    ("zwnj;", "\u{200C}"),
+    ("zwj;", "\u{200D}"),
+    ("lrm;", "\u{200E}"),
+    ("rlm;", "\u{200F}"),
+];
+
+#[test]
+fn passes_through_plain_text() {
+    assert_eq!(Decoder::new("hello, world!").run(), ("hello, world!".to_string(), vec![]));
+}
+
+#[test]
+fn replaces_null_only_when_enabled() {
+    assert_eq!(Decoder::new("a\0b").unsafe_null().run(), ("a\u{FFFD}b".to_string(), vec![]));
+    assert_eq!(Decoder::new("a\0b").run(), ("a\0b".to_string(), vec![]));
+}
+
+#[test]
+fn decodes_a_semicolon_terminated_named_reference() {
+    assert_eq!(Decoder::new("&amp;").run(), ("&".to_string(), vec![]));
+}
+
+#[test]
+fn decodes_a_legacy_named_reference_missing_its_semicolon() {
+    assert_eq!(
+        Decoder::new("&amp").run(),
+        ("&".to_string(), vec![ParseErrorCode::MissingSemicolonAfterCharacterReference])
+    );
+}
+
+#[test]
+fn leaves_an_unterminated_unmatched_name_as_literal_text_with_no_error() {
+    // Not `;`-terminated: the ambiguous-ampersand state doesn't raise an
+    // error here, per spec.
+    assert_eq!(Decoder::new("&zzzzzzz").run(), ("&zzzzzzz".to_string(), vec![]));
+}
+
+#[test]
+fn flags_a_semicolon_terminated_unmatched_name_as_unknown() {
+    assert_eq!(
+        Decoder::new("&zzzzzzz;").run(),
+        (
+            "&zzzzzzz;".to_string(),
+            vec![ParseErrorCode::UnknownNamedCharacterReference]
+        )
+    );
+}
+
+#[test]
+fn decodes_a_decimal_numeric_reference() {
+    assert_eq!(Decoder::new("&#65;").run(), ("A".to_string(), vec![]));
+}
+
+#[test]
+fn decodes_a_hex_numeric_reference() {
+    assert_eq!(Decoder::new("&#x41;").run(), ("A".to_string(), vec![]));
+}
+
+#[test]
+fn attr_entities_leaves_an_unterminated_legacy_reference_before_an_alphanumeric() {
+    // The longest match in `&noting` is the legacy `&not`; immediately
+    // followed by an alphanumeric/`=`, it is left as literal text in
+    // attribute values, unlike in text content.
+    assert_eq!(
+        Decoder::new("&noting").attr_entities().run(),
+        ("&noting".to_string(), vec![])
+    );
+}
+
+#[test]
+fn no_character_references_disables_decoding_entirely() {
+    assert_eq!(
+        Decoder::new("this is &amp; a comment")
+            .unsafe_null()
+            .no_character_references()
+            .run(),
+        ("this is &amp; a comment".to_string(), vec![])
+    );
+}
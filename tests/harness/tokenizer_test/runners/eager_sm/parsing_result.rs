@@ -1,29 +1,39 @@
 use cool_thing::tokenizer::{LexUnit, NextOutputType, TagPreview, TextParsingModeSnapshot};
 use cool_thing::transform_stream::TransformStream;
+use cool_thing::parse_error::ParseError;
 use cool_thing::Error;
 use harness::tokenizer_test::chunked_input::ChunkedInput;
 use harness::tokenizer_test::runners::BUFFER_SIZE;
 use harness::tokenizer_test::test_outputs::TestTagPreview;
+use token::{TestParseError, TestToken};
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 #[derive(Default)]
 pub struct ParsingResult {
     pub previews: Vec<TestTagPreview>,
+    pub errors: Vec<TestParseError>,
     pub has_bailout: bool,
     pending_tag_preview: Option<TestTagPreview>,
 }
 
 impl ParsingResult {
-    pub fn new(input: &ChunkedInput, initial_mode_snapshot: TextParsingModeSnapshot) -> Self {
+    pub fn new(
+        input: &ChunkedInput,
+        initial_mode_snapshot: TextParsingModeSnapshot,
+        last_start_tag_name: Option<&str>,
+    ) -> Self {
         let mut result = ParsingResult {
             previews: Vec::new(),
+            errors: Vec::new(),
             has_bailout: false,
             pending_tag_preview: None,
         };
 
         // TODO
-        result.has_bailout = result.parse(input, initial_mode_snapshot).is_err();
+        result.has_bailout = result
+            .parse(input, initial_mode_snapshot, last_start_tag_name)
+            .is_err();
 
         result
     }
@@ -32,11 +42,38 @@ impl ParsingResult {
         &mut self,
         input: &ChunkedInput,
         initial_mode_snapshot: TextParsingModeSnapshot,
+        last_start_tag_name: Option<&str>,
     ) -> Result<(), Error> {
         let result = Rc::new(RefCell::new(self));
         let pending_preview_confirmed = Rc::new(Cell::new(false));
-        let lex_unit_handler = |_: &LexUnit| {};
-        let tag_lex_unit_handler = |_: &LexUnit| NextOutputType::TagPreview;
+
+        // Neither handler cares about the decoded token itself here (this
+        // runner only tracks previews/errors/bailout), but converting
+        // through `TestToken::from_lex_unit` is still how a `LexUnit`'s
+        // character-reference decode errors (missing semicolon, unknown
+        // named reference, etc.) make it into the same `errors` list as
+        // the tokenizer-raised ones, so the two can be asserted together.
+        let lex_unit_handler = {
+            let result = Rc::clone(&result);
+
+            move |lex_unit: &mut LexUnit| result.borrow_mut().add_decode_errors(lex_unit)
+        };
+
+        let tag_lex_unit_handler = {
+            let result = Rc::clone(&result);
+
+            move |lex_unit: &mut LexUnit| {
+                result.borrow_mut().add_decode_errors(lex_unit);
+
+                NextOutputType::TagPreview
+            }
+        };
+
+        let parse_error_handler = {
+            let result = Rc::clone(&result);
+
+            move |error| result.borrow_mut().add_error(error)
+        };
 
         let tag_preview_handler = {
             let result = Rc::clone(&result);
@@ -53,11 +90,15 @@ impl ParsingResult {
             }
         };
 
+        let output_sink = |_: &[u8]| {};
+
         let mut transform_stream = TransformStream::new(
             BUFFER_SIZE,
             lex_unit_handler,
             tag_lex_unit_handler,
             tag_preview_handler,
+            parse_error_handler,
+            output_sink,
         );
 
         transform_stream
@@ -69,6 +110,12 @@ impl ParsingResult {
                 Box::new(move || pending_preview_confirmed.set(true))
             });
 
+        transform_stream.set_initial_text_parsing_mode(initial_mode_snapshot);
+
+        if let Some(last_start_tag_name) = last_start_tag_name {
+            transform_stream.set_last_start_tag_name(last_start_tag_name);
+        }
+
         input.parse(
             transform_stream,
             initial_mode_snapshot,
@@ -91,6 +138,14 @@ impl ParsingResult {
         self.previews.push(pending_preview);
     }
 
+    fn add_error(&mut self, error: ParseError) {
+        self.errors.push(TestParseError::from(error));
+    }
+
+    fn add_decode_errors(&mut self, lex_unit: &LexUnit) {
+        TestToken::from_lex_unit(lex_unit, &mut self.errors);
+    }
+
     fn add_tag_preview(&mut self, tag_preview: &TagPreview, pending_preview_confirmed: bool) {
         if pending_preview_confirmed {
             self.store_pending_preview();
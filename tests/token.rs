@@ -4,10 +4,65 @@ use std::fmt::{self, Formatter};
 use std::iter::FromIterator;
 use serde_json::error::Error;
 use super::unescape::Unescape;
-use cool_thing::{LexResult, TokenDescriptor};
+use cool_thing::tokenizer::{LexUnit, TextParsingMode, Token};
+use cool_thing::parse_error::{ParseError, ParseErrorCode};
 use super::decoder::Decoder;
 use std::str;
 
+/// Mirrors a single entry of the `errors` array in an html5lib-tests test
+/// case: a spec error code plus the line/column at which it was expected.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TestParseError {
+    pub code: String,
+    pub line: u32,
+    pub col: u32,
+}
+
+/// `Decoder`-sourced errors carry no real stream position (see
+/// `from_code`) and are recorded with `line`/`col` left at this sentinel.
+/// Comparing two such errors -- or a sentinel one against a real,
+/// positioned one for the same code -- falls back to comparing the code
+/// alone, since the sentinel side has nothing more specific to check.
+fn is_unknown_position(error: &TestParseError) -> bool {
+    error.line == 0 && error.col == 0
+}
+
+impl PartialEq for TestParseError {
+    fn eq(&self, other: &Self) -> bool {
+        if self.code != other.code {
+            return false;
+        }
+
+        is_unknown_position(self) || is_unknown_position(other) || (self.line == other.line && self.col == other.col)
+    }
+}
+
+impl From<ParseError> for TestParseError {
+    fn from(error: ParseError) -> Self {
+        TestParseError {
+            code: error.code.code().to_string(),
+            line: error.position.line,
+            col: error.position.col,
+        }
+    }
+}
+
+impl TestParseError {
+    /// Builds a `TestParseError` from a code raised by `Decoder`, which
+    /// works over an already-extracted raw slice and so has no notion of
+    /// the token's position within the overall document. `line`/`col` are
+    /// left at `0` as an explicit "unknown" sentinel rather than a real
+    /// stream position; `PartialEq` treats that sentinel as matching any
+    /// position for the same code.
+    fn from_code(code: ParseErrorCode) -> Self {
+        TestParseError {
+            code: code.code().to_string(),
+            line: 0,
+            col: 0,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Deserialize)]
 enum TokenKind {
     Character,
@@ -169,63 +224,97 @@ fn bytes_to_str(bytes: &[u8]) -> &str {
     unsafe { str::from_utf8_unchecked(bytes) }
 }
 
-fn bytes_to_string(bytes: &[u8]) -> String {
-    unsafe { String::from_utf8_unchecked(bytes.to_vec()) }
+impl<'u> From<&'u LexUnit> for TestToken {
+    /// Cheap conversion for callers that don't need decode errors surfaced
+    /// (e.g. just comparing token shape): any character-reference parse
+    /// errors `Decoder` raises along the way are silently discarded. Use
+    /// `from_lex_unit` to collect them instead.
+    fn from(lex_unit: &'u LexUnit) -> Self {
+        let mut discarded = Vec::new();
+
+        TestToken::from_lex_unit(lex_unit, &mut discarded)
+    }
 }
 
-impl<'r, 't> From<LexResult<'r, 't>> for TestToken {
-    fn from(lex_res: LexResult<'r, 't>) -> Self {
-        match (lex_res.token_descr, lex_res.raw) {
-            (TokenDescriptor::Character, Some(raw)) => TestToken::Character(bytes_to_string(raw)),
+impl TestToken {
+    /// Converts a tokenizer-produced `LexUnit` into the `TestToken` it's
+    /// expected to match, decoding raw character/comment/attribute text via
+    /// `Decoder` along the way. Any character-reference parse errors
+    /// `Decoder` raises while doing so (missing semicolon, unknown named
+    /// reference, etc.) are appended to `errors` so callers can merge them
+    /// into the same `errors` array comparison used for tokenizer-raised
+    /// `ParseError`s.
+    pub fn from_lex_unit(lex_unit: &LexUnit, errors: &mut Vec<TestParseError>) -> Self {
+        match lex_unit.token {
+            Token::Character => {
+                // Only Data/RCDATA decode character references; RAWTEXT,
+                // PLAINTEXT, script data and CDATA emit their text verbatim.
+                match lex_unit.mode {
+                    TextParsingMode::Data | TextParsingMode::Rcdata => {
+                        let (text, decode_errors) = Decoder::new(bytes_to_str(&lex_unit.raw))
+                            .unsafe_null()
+                            .run();
+
+                        errors.extend(decode_errors.into_iter().map(TestParseError::from_code));
 
-            (TokenDescriptor::Comment, Some(raw)) => {
-                TestToken::Comment(Decoder::new(bytes_to_str(raw)).unsafe_null().run())
+                        TestToken::Character(text)
+                    }
+                    TextParsingMode::PlainText
+                    | TextParsingMode::Rawtext
+                    | TextParsingMode::ScriptData
+                    | TextParsingMode::CData => {
+                        TestToken::Character(bytes_to_str(&lex_unit.raw).to_string())
+                    }
+                }
             }
 
-            (
-                TokenDescriptor::StartTag {
-                    name,
-                    attributes,
-                    self_closing,
-                },
-                Some(raw),
-            ) => TestToken::StartTag {
-                name: name.as_string(raw),
-
-                attributes: HashMap::from_iter(attributes.iter().rev().map(|attr| {
-                    (
-                        name.as_string(raw),
-                        Decoder::new(attr.value.as_str(raw))
-                            .unsafe_null()
-                            .attr_entities()
-                            .run(),
-                    )
+            Token::Comment => {
+                // Comments never undergo character-reference decoding per
+                // spec -- only the NUL replacement applies.
+                let (text, decode_errors) = Decoder::new(bytes_to_str(&lex_unit.raw))
+                    .unsafe_null()
+                    .no_character_references()
+                    .run();
+
+                errors.extend(decode_errors.into_iter().map(TestParseError::from_code));
+
+                TestToken::Comment(text)
+            }
+
+            Token::StartTag {
+                ref name,
+                ref attributes,
+                self_closing,
+            } => TestToken::StartTag {
+                name: name.clone(),
+
+                attributes: HashMap::from_iter(attributes.iter().map(|(attr_name, value)| {
+                    let (value, decode_errors) = Decoder::new(value)
+                        .unsafe_null()
+                        .attr_entities()
+                        .run();
+
+                    errors.extend(decode_errors.into_iter().map(TestParseError::from_code));
+
+                    (attr_name.clone(), value)
                 })),
 
                 self_closing,
             },
 
-            (TokenDescriptor::EndTag { name }, Some(raw)) => TestToken::EndTag {
-                name: name.as_string(raw),
-            },
+            Token::EndTag { ref name } => TestToken::EndTag { name: name.clone() },
 
-            (
-                TokenDescriptor::Doctype {
-                    name,
-                    public_id,
-                    system_id,
-                    force_quirks,
-                },
-                Some(raw),
-            ) => TestToken::Doctype {
-                name: name.as_ref().map(|s| s.as_string(raw)),
-                public_id: public_id.as_ref().map(|s| s.as_string(raw)),
-                system_id: system_id.as_ref().map(|s| s.as_string(raw)),
+            Token::Doctype {
+                ref name,
+                force_quirks,
+            } => TestToken::Doctype {
+                name: name.clone(),
+                public_id: None,
+                system_id: None,
                 force_quirks,
             },
 
-            (TokenDescriptor::Eof, None) => TestToken::Eof,
-            _ => unreachable!(),
+            Token::Eof => TestToken::Eof,
         }
     }
 }
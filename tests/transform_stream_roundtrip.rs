@@ -0,0 +1,244 @@
+extern crate cool_thing;
+
+use cool_thing::tokenizer::{LexUnit, NextOutputType, TagPreview, TextParsingMode, TextParsingModeSnapshot, Token};
+use cool_thing::transform_stream::TransformStream;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const BUFFER_SIZE: usize = 256;
+
+/// Feeds `input` to a fresh `TransformStream` split at `chunk_boundaries`
+/// (byte offsets, ascending) and returns everything the output sink saw,
+/// concatenated.
+fn rewrite_in_chunks(input: &[u8], chunk_boundaries: &[usize]) -> Vec<u8> {
+    let output = Rc::new(RefCell::new(Vec::new()));
+
+    let output_sink = {
+        let output = Rc::clone(&output);
+
+        move |bytes: &[u8]| output.borrow_mut().extend_from_slice(bytes)
+    };
+
+    let mut transform_stream = TransformStream::new(
+        BUFFER_SIZE,
+        |_: &mut LexUnit| {},
+        |_: &mut LexUnit| NextOutputType::LexUnit,
+        |_: &TagPreview| NextOutputType::LexUnit,
+        |_| {},
+        output_sink,
+    );
+
+    let mut start = 0;
+
+    for &boundary in chunk_boundaries {
+        transform_stream.write(&input[start..boundary]).unwrap();
+        start = boundary;
+    }
+
+    transform_stream.write(&input[start..]).unwrap();
+    transform_stream.end().unwrap();
+
+    // `output_sink` (and the `Rc` clone it holds) lives inside
+    // `transform_stream`, so the strong count won't drop to 1 until it does.
+    drop(transform_stream);
+
+    Rc::try_unwrap(output).unwrap().into_inner()
+}
+
+fn assert_roundtrips_at_every_boundary(input: &str) {
+    let input = input.as_bytes();
+
+    for boundary in 1..input.len() {
+        let output = rewrite_in_chunks(input, &[boundary]);
+
+        assert_eq!(
+            output, input,
+            "identity rewrite mismatch when split at byte {}",
+            boundary
+        );
+    }
+}
+
+#[test]
+fn identity_rewrite_reproduces_plain_text() {
+    assert_roundtrips_at_every_boundary("Hello, world!");
+}
+
+#[test]
+fn identity_rewrite_reproduces_tags_and_attributes() {
+    assert_roundtrips_at_every_boundary(
+        r#"<div class="foo" id='bar'><p>Some &amp; text</p></div>"#,
+    );
+}
+
+#[test]
+fn identity_rewrite_reproduces_comments_and_doctype() {
+    assert_roundtrips_at_every_boundary("<!DOCTYPE html><!-- a comment --><br>");
+}
+
+#[test]
+fn identity_rewrite_reproduces_across_many_chunk_boundaries() {
+    let input = b"<html><head><title>Title</title></head><body>Text</body></html>";
+    let output = rewrite_in_chunks(input, &[5, 12, 20, 33, 40, 55]);
+
+    assert_eq!(output, input);
+}
+
+/// A handler that mutates every non-tag `LexUnit`'s `output` gets its
+/// replacement, rather than the original bytes, written to the sink.
+#[test]
+fn lex_unit_handler_mutation_is_reflected_in_output() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+
+    let output_sink = {
+        let output = Rc::clone(&output);
+
+        move |bytes: &[u8]| output.borrow_mut().extend_from_slice(bytes)
+    };
+
+    let mut transform_stream = TransformStream::new(
+        BUFFER_SIZE,
+        |lex_unit: &mut LexUnit| lex_unit.output = Some(lex_unit.raw.to_ascii_uppercase()),
+        |_: &mut LexUnit| NextOutputType::LexUnit,
+        |_: &TagPreview| NextOutputType::LexUnit,
+        |_| {},
+        output_sink,
+    );
+
+    transform_stream
+        .write(b"<!-- a comment --><p>some text</p>")
+        .unwrap();
+    transform_stream.end().unwrap();
+
+    drop(transform_stream);
+
+    assert_eq!(
+        Rc::try_unwrap(output).unwrap().into_inner(),
+        b"<!-- A COMMENT --><p>SOME TEXT</p>".to_vec()
+    );
+}
+
+/// Seeds `TransformStream` with a RAWTEXT initial state and a last start
+/// tag name of `script`, as the harness does for html5lib-tests cases whose
+/// `initialStates`/`lastStartTag` fields require tokenizing doesn't start
+/// out in Data mode. `<div>` inside the RAWTEXT run is plain text, not a
+/// tag, and only the matching `</script>` end tag -- recognised from the
+/// very first byte, with no preceding start tag ever observed -- switches
+/// the tokenizer back to Data.
+#[test]
+fn initial_text_parsing_mode_and_last_start_tag_name_drive_rawtext_matching() {
+    let tokens = Rc::new(RefCell::new(Vec::new()));
+
+    let lex_unit_handler = {
+        let tokens = Rc::clone(&tokens);
+
+        move |lex_unit: &mut LexUnit| tokens.borrow_mut().push(lex_unit.token.clone())
+    };
+
+    // `StartTag`/`EndTag` lex units are routed through `tag_lex_unit_handler`,
+    // not `lex_unit_handler` -- both must feed `tokens` to see every token.
+    let tag_lex_unit_handler = {
+        let tokens = Rc::clone(&tokens);
+
+        move |lex_unit: &mut LexUnit| {
+            tokens.borrow_mut().push(lex_unit.token.clone());
+
+            NextOutputType::LexUnit
+        }
+    };
+
+    let mut transform_stream = TransformStream::new(
+        BUFFER_SIZE,
+        lex_unit_handler,
+        tag_lex_unit_handler,
+        |_: &TagPreview| NextOutputType::LexUnit,
+        |_| {},
+        |_: &[u8]| {},
+    );
+
+    transform_stream.set_initial_text_parsing_mode(TextParsingModeSnapshot::new(TextParsingMode::Rawtext));
+    transform_stream.set_last_start_tag_name("script");
+
+    transform_stream
+        .write(b"<div>not a tag</script>after")
+        .unwrap();
+    transform_stream.end().unwrap();
+
+    let tokens = Rc::try_unwrap(tokens).unwrap().into_inner();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Character,
+            Token::EndTag {
+                name: "script".to_string()
+            },
+            Token::Character,
+            Token::Eof,
+        ]
+    );
+}
+
+/// A mismatching end tag name is not recognised as the end of RAWTEXT
+/// content: without an observed start tag to match against, only the exact
+/// `last_start_tag_name` ends the run, so `</div>` stays literal text.
+#[test]
+fn mismatching_end_tag_name_does_not_end_rawtext_content() {
+    let tokens = Rc::new(RefCell::new(Vec::new()));
+
+    let lex_unit_handler = {
+        let tokens = Rc::clone(&tokens);
+
+        move |lex_unit: &mut LexUnit| tokens.borrow_mut().push(lex_unit.token.clone())
+    };
+
+    // `StartTag`/`EndTag` lex units are routed through `tag_lex_unit_handler`,
+    // not `lex_unit_handler` -- both must feed `tokens` to see every token.
+    let tag_lex_unit_handler = {
+        let tokens = Rc::clone(&tokens);
+
+        move |lex_unit: &mut LexUnit| {
+            tokens.borrow_mut().push(lex_unit.token.clone());
+
+            NextOutputType::LexUnit
+        }
+    };
+
+    let mut transform_stream = TransformStream::new(
+        BUFFER_SIZE,
+        lex_unit_handler,
+        tag_lex_unit_handler,
+        |_: &TagPreview| NextOutputType::LexUnit,
+        |_| {},
+        |_: &[u8]| {},
+    );
+
+    transform_stream.set_initial_text_parsing_mode(TextParsingModeSnapshot::new(TextParsingMode::Rawtext));
+    transform_stream.set_last_start_tag_name("script");
+
+    transform_stream.write(b"</div></script>").unwrap();
+    transform_stream.end().unwrap();
+
+    let tokens = Rc::try_unwrap(tokens).unwrap().into_inner();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Character,
+            Token::EndTag {
+                name: "script".to_string()
+            },
+            Token::Eof,
+        ]
+    );
+}
+
+/// An unterminated tag at EOF never resolves into a token (and so is
+/// correctly dropped, per spec, along with its `EofInTag` error), but the
+/// text preceding it is independently complete and must still make it out.
+#[test]
+fn text_preceding_unterminated_tag_at_eof_is_still_flushed() {
+    let output = rewrite_in_chunks(b"hello<div foo", &[]);
+
+    assert_eq!(output, b"hello".to_vec());
+}